@@ -0,0 +1,134 @@
+/// A set of `char`s represented as a sorted, non-overlapping list of
+/// inclusive ranges, optionally negated (complemented). This is the single
+/// representation the matching engine uses for anything that boils down to
+/// "does this one character match": bracket expressions (`[abc]`, `[a-z]`,
+/// `[^a-z]`), and escape shorthand classes (`\d`, `\D`, `\w`, `\W`, `\s`,
+/// `\S`).
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CharClass {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl CharClass {
+    pub(crate) fn empty() -> Self {
+        CharClass { ranges: Vec::new(), negated: false }
+    }
+
+    pub(crate) fn single(c: char) -> Self {
+        CharClass { ranges: vec![(c, c)], negated: false }
+    }
+
+    pub(crate) fn range(lo: char, hi: char) -> Self {
+        CharClass { ranges: vec![(lo, hi)], negated: false }
+    }
+
+    /// Combines `self` and `other` into the class containing every char
+    /// accepted by either. Both must be non-negated — negate the result
+    /// afterwards instead, so there's only ever one place complementing
+    /// happens.
+    pub(crate) fn union(mut self, other: CharClass) -> Self {
+        debug_assert!(!self.negated && !other.negated, "union combines un-negated classes; negate the result instead");
+        self.ranges.extend(other.ranges);
+        self.ranges.sort_unstable();
+
+        let mut merged: Vec<(char, char)> = Vec::new();
+        for (lo, hi) in self.ranges {
+            match merged.last_mut() {
+                Some((_, last_hi)) if lo as u32 <= *last_hi as u32 + 1 => {
+                    if hi > *last_hi {
+                        *last_hi = hi;
+                    }
+                },
+                _ => merged.push((lo, hi)),
+            }
+        }
+        CharClass { ranges: merged, negated: false }
+    }
+
+    /// Complements the class: a char matches iff it previously didn't.
+    pub(crate) fn negate(mut self) -> Self {
+        self.negated = !self.negated;
+        self
+    }
+
+    pub(crate) fn contains(&self, c: char) -> bool {
+        let in_ranges = self.ranges.iter().any(|&(lo, hi)| c >= lo && c <= hi);
+        in_ranges != self.negated
+    }
+
+    /// `.` — matches any character.
+    pub(crate) fn any() -> Self {
+        CharClass::empty().negate()
+    }
+
+    /// `\d` — ASCII digits.
+    pub(crate) fn digit() -> Self {
+        CharClass::range('0', '9')
+    }
+
+    /// `\w` — word characters: ASCII letters, digits, and underscore.
+    pub(crate) fn word() -> Self {
+        CharClass::range('a', 'z')
+            .union(CharClass::range('A', 'Z'))
+            .union(CharClass::digit())
+            .union(CharClass::single('_'))
+    }
+
+    /// `\s` — whitespace: space, tab, newline, carriage return, and the
+    /// vertical/form-feed control characters.
+    pub(crate) fn whitespace() -> Self {
+        [' ', '\t', '\n', '\r', '\u{0B}', '\u{0C}']
+            .into_iter()
+            .fold(CharClass::empty(), |acc, c| acc.union(CharClass::single(c)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CharClass;
+
+    #[test]
+    fn charclass_single_and_range_contain() {
+        assert!(CharClass::single('a').contains('a'));
+        assert!(!CharClass::single('a').contains('b'));
+        assert!(CharClass::range('a', 'z').contains('m'));
+        assert!(!CharClass::range('a', 'z').contains('A'));
+    }
+
+    #[test]
+    fn charclass_union_merges_overlapping_and_adjacent_ranges() {
+        let class = CharClass::range('a', 'c').union(CharClass::range('d', 'f'));
+        assert_eq!(class, CharClass::range('a', 'f'));
+
+        let class = CharClass::range('a', 'm').union(CharClass::range('h', 'z'));
+        assert_eq!(class, CharClass::range('a', 'z'));
+    }
+
+    #[test]
+    fn charclass_negate_complements_membership() {
+        let class = CharClass::range('a', 'z').negate();
+        assert!(!class.contains('m'));
+        assert!(class.contains('M'));
+    }
+
+    #[test]
+    fn charclass_any_contains_everything() {
+        assert!(CharClass::any().contains('a'));
+        assert!(CharClass::any().contains('\n'));
+        assert!(CharClass::any().contains('9'));
+    }
+
+    #[test]
+    fn charclass_digit_word_whitespace() {
+        assert!(CharClass::digit().contains('5'));
+        assert!(!CharClass::digit().contains('a'));
+
+        assert!(CharClass::word().contains('_'));
+        assert!(CharClass::word().contains('Z'));
+        assert!(!CharClass::word().contains(' '));
+
+        assert!(CharClass::whitespace().contains('\t'));
+        assert!(!CharClass::whitespace().contains('a'));
+    }
+}
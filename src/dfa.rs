@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use crate::nfa::Nfa;
+
+/// Maximum number of distinct NFA state-sets the lazy DFA will materialize
+/// before it gives up on the cache for the current match and reruns that
+/// match from the start with the plain NFA simulator. Bounds memory for patterns like `[a-z]*` or `.*`
+/// whose reachable state-sets can otherwise grow without limit over a large
+/// input alphabet.
+const MAX_STATES: usize = 512;
+
+/// A DFA built lazily from an `Nfa` via powerset/subset construction: each
+/// DFA state is a deduplicated set of NFA state ids, realized only once an
+/// input character actually drives a transition into it. This avoids
+/// enumerating the whole alphabet up front, which is what makes it tractable
+/// for patterns with wide character classes.
+#[derive(Debug)]
+pub(crate) struct Dfa {
+    nfa: Nfa,
+    states: Vec<Vec<usize>>,
+    index: HashMap<Vec<usize>, usize>,
+    transitions: HashMap<(usize, char, bool), usize>,
+}
+
+impl Dfa {
+    pub(crate) fn new(nfa: Nfa) -> Self {
+        Dfa { nfa, states: Vec::new(), index: HashMap::new(), transitions: HashMap::new() }
+    }
+
+    /// Returns the id for `set`, realizing it as a new DFA state if it
+    /// hasn't been seen before. Returns `None` if the state budget is
+    /// exhausted, signalling the cache should be abandoned for this match.
+    fn state_id(&mut self, set: Vec<usize>) -> Option<usize> {
+        if let Some(&id) = self.index.get(&set) {
+            return Some(id);
+        }
+        if self.states.len() >= MAX_STATES {
+            return None;
+        }
+        let id = self.states.len();
+        self.index.insert(set.clone(), id);
+        self.states.push(set);
+        Some(id)
+    }
+
+    /// Attempts to decide `haystack` purely via the DFA, materializing
+    /// states and transitions on demand. Returns `None` as soon as the
+    /// cache's state budget runs out, rather than guessing.
+    fn try_match(&mut self, haystack: &str) -> Option<bool> {
+        let chars: Vec<char> = haystack.chars().collect();
+        let len = chars.len();
+
+        let mut current = self.state_id(self.nfa.closure_from_start(len == 0))?;
+
+        for (i, &c) in chars.iter().enumerate() {
+            let at_end = i + 1 == len;
+            if let Some(&next) = self.transitions.get(&(current, c, at_end)) {
+                current = next;
+                continue;
+            }
+            let set = self.nfa.step(&self.states[current], c, at_end);
+            let next = self.state_id(set)?;
+            self.transitions.insert((current, c, at_end), next);
+            current = next;
+        }
+
+        Some(self.nfa.is_accepting(&self.states[current]))
+    }
+
+    /// The fast path for matching: tries the cached DFA first, falling back
+    /// to the uncached NFA simulator if the state budget was exceeded
+    /// partway through (the cache is effectively "disabled" for that run).
+    pub(crate) fn is_match(&mut self, haystack: &str) -> bool {
+        self.try_match(haystack).unwrap_or_else(|| self.nfa.simulate(haystack))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dfa, MAX_STATES};
+    use crate::nfa::Nfa;
+    use crate::parser::Parser;
+
+    fn compile(pattern: &str) -> Nfa {
+        let tokens = crate::lexer::lex(pattern).unwrap();
+        let expression = Parser::new(pattern, tokens).parse().unwrap();
+        Nfa::compile(&expression)
+    }
+
+    #[test]
+    fn dfa_matches_like_nfa() {
+        let mut dfa = Dfa::new(compile("a*b"));
+        assert!(dfa.is_match("aaab"));
+        assert!(dfa.is_match("b"));
+        assert!(!dfa.is_match("aaa"));
+    }
+
+    #[test]
+    fn dfa_reuses_cached_states_across_calls() {
+        let mut dfa = Dfa::new(compile("(a|b)*c"));
+        assert!(dfa.is_match("ababc"));
+        assert!(dfa.is_match("c"));
+        assert!(!dfa.is_match("ababd"));
+        // Running the same pattern repeatedly should not blow past the
+        // bounded cache: only a handful of distinct state-sets are reachable.
+        assert!(dfa.states.len() < MAX_STATES);
+    }
+
+    #[test]
+    fn dfa_transition_cache_keys_on_at_end() {
+        // "a$|ab": matching "ab" first must not poison the cached transition
+        // for 'a' out of the start state, or a later match of plain "a"
+        // (which needs the end-anchored branch) would wrongly reuse it.
+        let mut dfa = Dfa::new(compile("a$|ab"));
+        assert!(dfa.is_match("ab"));
+        assert!(dfa.is_match("a"));
+    }
+
+    #[test]
+    fn dfa_falls_back_to_nfa_when_cache_is_exhausted() {
+        let mut dfa = Dfa::new(compile("[a-z]+"));
+        dfa.states.truncate(0);
+        dfa.index.clear();
+        // Force the cache to look full so every lookup must fall back.
+        for i in 0..MAX_STATES {
+            dfa.states.push(vec![i]);
+        }
+        assert!(dfa.is_match("hello"));
+        assert!(!dfa.is_match("HELLO"));
+    }
+}
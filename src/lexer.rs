@@ -46,54 +46,99 @@ impl Token {
     }
 }
 
-/// lex transforms the given raw regex expression into a vector of tokens
-/// that is amenable to parsing.
-pub fn lex(expression: &str) -> Result<Vec<Token>, String> {
+/// Errors produced while lexing a regex expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LexError {
+    /// A `\` appeared with no following character to escape.
+    DanglingEscape,
+    /// `char` at byte offset `usize` is not a recognized token and was not escaped.
+    UnexpectedChar(char, usize),
+    /// A `[...]` character class was opened but never closed.
+    UnterminatedClass,
+}
+
+impl std::fmt::Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LexError::DanglingEscape => write!(f, "Invalid escape sequence"),
+            LexError::UnexpectedChar(c, _) => write!(f, "Unexpected character: '{}'", c),
+            LexError::UnterminatedClass => write!(f, "Unterminated character class"),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
+/// A half-open byte-offset range `[start, end)` into the original expression
+/// that a token was lexed from. Used to render positioned error messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// Renders a two-line caret-underline snippet pointing at this span within
+    /// `expression`, e.g. for `a*b@` with a span over the `@`:
+    ///
+    /// ```text
+    /// a*b@
+    ///    ^
+    /// ```
+    pub fn render_caret(&self, expression: &str) -> String {
+        let underline: String = (0..self.end)
+            .map(|i| if i < self.start { ' ' } else { '^' })
+            .collect();
+        format!("{}\n{}", expression, underline)
+    }
+}
+
+/// lex transforms the given raw regex expression into a vector of
+/// (token, span) pairs that is amenable to parsing, where each span records
+/// the byte offsets in `expression` the token was lexed from.
+pub fn lex(expression: &str) -> Result<Vec<(Token, Span)>, LexError> {
     let mut tokens = Vec::new();
 
-    // need to be able to peek at the next character without consuming it.
-    let mut iter = expression.chars().peekable();
-    loop {
-        let c = match iter.next() {
-            Some(c) => c,
-            None => break,
-        };
+    let mut iter = expression.char_indices();
+    while let Some((start, c)) = iter.next() {
+        let end = start + c.len_utf8();
         match c {
-            '*' => tokens.push(Token::KleeneStar),
-            '+' => tokens.push(Token::KleenePlus),
-            '?' => tokens.push(Token::Question),
-            '(' => tokens.push(Token::LParen),
-            ')' => tokens.push(Token::RParen),
-            '|' => tokens.push(Token::VBar),
-            '[' => tokens.push(Token::LBracket),
-            ']' => tokens.push(Token::RBracket),
-            '{' => tokens.push(Token::LBrace),
-            '}' => tokens.push(Token::RBrace),
-            ',' => tokens.push(Token::Comma),
-            '.' => tokens.push(Token::Dot),
-            '^' => tokens.push(Token::Caret),
-            '$' => tokens.push(Token::Dollar),
-            '-' => tokens.push(Token::Dash),
+            '*' => tokens.push((Token::KleeneStar, Span::new(start, end))),
+            '+' => tokens.push((Token::KleenePlus, Span::new(start, end))),
+            '?' => tokens.push((Token::Question, Span::new(start, end))),
+            '(' => tokens.push((Token::LParen, Span::new(start, end))),
+            ')' => tokens.push((Token::RParen, Span::new(start, end))),
+            '|' => tokens.push((Token::VBar, Span::new(start, end))),
+            '[' => tokens.push((Token::LBracket, Span::new(start, end))),
+            ']' => tokens.push((Token::RBracket, Span::new(start, end))),
+            '{' => tokens.push((Token::LBrace, Span::new(start, end))),
+            '}' => tokens.push((Token::RBrace, Span::new(start, end))),
+            ',' => tokens.push((Token::Comma, Span::new(start, end))),
+            '.' => tokens.push((Token::Dot, Span::new(start, end))),
+            '^' => tokens.push((Token::Caret, Span::new(start, end))),
+            '$' => tokens.push((Token::Dollar, Span::new(start, end))),
+            '-' => tokens.push((Token::Dash, Span::new(start, end))),
             '\\' => {
                 match iter.next() {
-                    Some(next) => tokens.push(Token::Backslash(next)),
-                    None => return Err("Invalid escape sequence".into()),
+                    Some((_, next)) => {
+                        tokens.push((Token::Backslash(next), Span::new(start, end + next.len_utf8())))
+                    },
+                    None => return Err(LexError::DanglingEscape),
                 }
             },
             _ if c.is_alphanumeric() || c.is_whitespace() => {
-                let mut literal = String::new();
-                literal.push(c);
-                while let Some(next) = iter.peek() {
-                    if next.is_alphanumeric() || next.is_whitespace() {
-                        literal.push(iter.next().unwrap());
-                    } else {
-                        break;
-                    }
-                }
-                tokens.push(Token::Literal(literal));
+                // Each character gets its own `Literal` token rather than merging runs
+                // together: quantifiers (`*`, `+`, `?`) bind to a single preceding token,
+                // so `ab*` must lex as `Literal("a")`, `Literal("b")`, `KleeneStar`, not
+                // `Literal("ab")`, `KleeneStar`.
+                tokens.push((Token::Literal(c.to_string()), Span::new(start, end)));
             }
             _ => {
-                return Err(format!("Unexpected character: '{}'", c));
+                return Err(LexError::UnexpectedChar(c, start));
             }
         }
     }
@@ -101,8 +146,9 @@ pub fn lex(expression: &str) -> Result<Vec<Token>, String> {
     Ok(tokens)
 }
 
+#[cfg(test)]
 mod tests {
-    use crate::lexer::{Token, lex};
+    use crate::lexer::{lex, LexError, Span, Token};
 
     #[test]
     fn test_token_is_binary_operator() {
@@ -130,61 +176,90 @@ mod tests {
     fn regex_lex() {
         let tokens = lex("a*b").unwrap();
         assert_eq!(tokens.len(), 3);
-        assert!(matches!(tokens[0], Token::Literal(ref s) if s == "a"));
-        assert!(matches!(tokens[1], Token::KleeneStar));
-        assert!(matches!(tokens[2], Token::Literal(ref s) if s == "b"));
+        assert!(matches!(tokens[0].0, Token::Literal(ref s) if s == "a"));
+        assert!(matches!(tokens[1].0, Token::KleeneStar));
+        assert!(matches!(tokens[2].0, Token::Literal(ref s) if s == "b"));
+    }
+
+    #[test]
+    fn regex_lex_spans() {
+        let tokens = lex("a*b").unwrap();
+        assert_eq!(tokens[0].1, Span::new(0, 1));
+        assert_eq!(tokens[1].1, Span::new(1, 2));
+        assert_eq!(tokens[2].1, Span::new(2, 3));
     }
 
     #[test]
     fn regex_lex_everything() {
         let tokens = lex("a*b+c?d|e(f|g)[h-i]{2,3},j.k^l$m").unwrap();
         assert_eq!(tokens.len(), 32);
-        assert!(matches!(tokens[0], Token::Literal(ref s) if s == "a"));
-        assert!(matches!(tokens[1], Token::KleeneStar));
-        assert!(matches!(tokens[2], Token::Literal(ref s) if s == "b"));
-        assert!(matches!(tokens[3], Token::KleenePlus));
-        assert!(matches!(tokens[4], Token::Literal(ref s) if s == "c"));
-        assert!(matches!(tokens[5], Token::Question));
-        assert!(matches!(tokens[6], Token::Literal(ref s) if s == "d"));
-        assert!(matches!(tokens[7], Token::VBar));
-        assert!(matches!(tokens[8], Token::Literal(ref s) if s == "e"));
-        assert!(matches!(tokens[9], Token::LParen));
-        assert!(matches!(tokens[10], Token::Literal(ref s) if s == "f"));
-        assert!(matches!(tokens[11], Token::VBar));
-        assert!(matches!(tokens[12], Token::Literal(ref s) if s == "g"));
-        assert!(matches!(tokens[13], Token::RParen));
-        assert!(matches!(tokens[14], Token::LBracket));
-        assert!(matches!(tokens[15], Token::Literal(ref s) if s == "h"));
-        assert!(matches!(tokens[16], Token::Dash));
-        assert!(matches!(tokens[17], Token::Literal(ref s) if s == "i"));
-        assert!(matches!(tokens[18], Token::RBracket));
-        assert!(matches!(tokens[19], Token::LBrace));
-        assert!(matches!(tokens[20], Token::Literal(ref s) if s == "2"));
-        assert!(matches!(tokens[21], Token::Comma));
-        assert!(matches!(tokens[22], Token::Literal(ref s) if s == "3"));
-        assert!(matches!(tokens[23], Token::RBrace));
-        assert!(matches!(tokens[24], Token::Comma));
-        assert!(matches!(tokens[25], Token::Literal(ref s) if s == "j"));
-        assert!(matches!(tokens[26], Token::Dot));
-        assert!(matches!(tokens[27], Token::Literal(ref s) if s == "k"));
-        assert!(matches!(tokens[28], Token::Caret));
-        assert!(matches!(tokens[29], Token::Literal(ref s) if s == "l"));
-        assert!(matches!(tokens[30], Token::Dollar));
-        assert!(matches!(tokens[31], Token::Literal(ref s) if s == "m"));
+        assert!(matches!(tokens[0].0, Token::Literal(ref s) if s == "a"));
+        assert!(matches!(tokens[1].0, Token::KleeneStar));
+        assert!(matches!(tokens[2].0, Token::Literal(ref s) if s == "b"));
+        assert!(matches!(tokens[3].0, Token::KleenePlus));
+        assert!(matches!(tokens[4].0, Token::Literal(ref s) if s == "c"));
+        assert!(matches!(tokens[5].0, Token::Question));
+        assert!(matches!(tokens[6].0, Token::Literal(ref s) if s == "d"));
+        assert!(matches!(tokens[7].0, Token::VBar));
+        assert!(matches!(tokens[8].0, Token::Literal(ref s) if s == "e"));
+        assert!(matches!(tokens[9].0, Token::LParen));
+        assert!(matches!(tokens[10].0, Token::Literal(ref s) if s == "f"));
+        assert!(matches!(tokens[11].0, Token::VBar));
+        assert!(matches!(tokens[12].0, Token::Literal(ref s) if s == "g"));
+        assert!(matches!(tokens[13].0, Token::RParen));
+        assert!(matches!(tokens[14].0, Token::LBracket));
+        assert!(matches!(tokens[15].0, Token::Literal(ref s) if s == "h"));
+        assert!(matches!(tokens[16].0, Token::Dash));
+        assert!(matches!(tokens[17].0, Token::Literal(ref s) if s == "i"));
+        assert!(matches!(tokens[18].0, Token::RBracket));
+        assert!(matches!(tokens[19].0, Token::LBrace));
+        assert!(matches!(tokens[20].0, Token::Literal(ref s) if s == "2"));
+        assert!(matches!(tokens[21].0, Token::Comma));
+        assert!(matches!(tokens[22].0, Token::Literal(ref s) if s == "3"));
+        assert!(matches!(tokens[23].0, Token::RBrace));
+        assert!(matches!(tokens[24].0, Token::Comma));
+        assert!(matches!(tokens[25].0, Token::Literal(ref s) if s == "j"));
+        assert!(matches!(tokens[26].0, Token::Dot));
+        assert!(matches!(tokens[27].0, Token::Literal(ref s) if s == "k"));
+        assert!(matches!(tokens[28].0, Token::Caret));
+        assert!(matches!(tokens[29].0, Token::Literal(ref s) if s == "l"));
+        assert!(matches!(tokens[30].0, Token::Dollar));
+        assert!(matches!(tokens[31].0, Token::Literal(ref s) if s == "m"));
     }
 
     #[test]
     fn regex_lex_invalid() {
         let result = lex(r"a*b\");
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Invalid escape sequence");
+        assert_eq!(result.unwrap_err(), LexError::DanglingEscape);
+    }
+
+    #[test]
+    fn regex_lex_invalid_display() {
+        assert_eq!(LexError::DanglingEscape.to_string(), "Invalid escape sequence");
     }
 
     #[test]
     fn regex_lex_unsupported_character() {
         // '@' should be escaped
         let result = lex("a*b@");
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Unexpected character: '@'");
+        assert_eq!(result.unwrap_err(), LexError::UnexpectedChar('@', 3));
+    }
+
+    #[test]
+    fn regex_lex_unsupported_character_display() {
+        assert_eq!(
+            LexError::UnexpectedChar('@', 3).to_string(),
+            "Unexpected character: '@'"
+        );
+    }
+
+    #[test]
+    fn regex_lex_unsupported_character_renders_caret() {
+        let input = "a*b@";
+        let span = match lex(input).unwrap_err() {
+            LexError::UnexpectedChar(c, start) => Span::new(start, start + c.len_utf8()),
+            other => panic!("expected LexError::UnexpectedChar, got {:?}", other),
+        };
+        assert_eq!(span.render_caret(input), "a*b@\n   ^");
     }
 }
@@ -0,0 +1,399 @@
+use crate::lexer::Token;
+use crate::parser::Expression;
+#[cfg(test)]
+use crate::parser::{ParseError, Parser};
+
+type StateId = usize;
+
+/// A state in a Thompson-constructed NFA. `Char` consumes one input
+/// character satisfying its predicate and advances to `next`; `Split` takes
+/// both outgoing epsilon edges; `StartAnchor`/`EndAnchor` are zero-width
+/// assertions for `^`/`$` that only proceed to `next` at the start/end of
+/// the haystack; `Match` is the unique accepting state.
+enum State {
+    Char(Box<dyn Fn(char) -> bool>, StateId),
+    Split(StateId, StateId),
+    StartAnchor(StateId),
+    EndAnchor(StateId),
+    Match,
+}
+
+/// Hand-written since `Char`'s predicate is a `Box<dyn Fn>`, which has no
+/// `Debug` impl to derive; the predicate itself is opaque, so it's elided.
+impl std::fmt::Debug for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            State::Char(_, next) => f.debug_tuple("Char").field(&"<predicate>").field(next).finish(),
+            State::Split(a, b) => f.debug_tuple("Split").field(a).field(b).finish(),
+            State::StartAnchor(next) => f.debug_tuple("StartAnchor").field(next).finish(),
+            State::EndAnchor(next) => f.debug_tuple("EndAnchor").field(next).finish(),
+            State::Match => write!(f, "Match"),
+        }
+    }
+}
+
+/// An un-patched outgoing edge of a fragment under construction, recording
+/// which field of which state still needs to be wired to the next fragment.
+enum Dangling {
+    CharNext(StateId),
+    Split1(StateId),
+    Split2(StateId),
+    StartAnchorNext(StateId),
+    EndAnchorNext(StateId),
+}
+
+/// A partially built chunk of the NFA: `start` is its entry state and
+/// `dangling` is the list of outgoing edges still waiting to be patched to
+/// whatever comes next.
+struct Frag {
+    start: StateId,
+    dangling: Vec<Dangling>,
+}
+
+/// Compiles an `Expression` tree into a Thompson NFA, one AST node at a time.
+struct Compiler {
+    states: Vec<State>,
+}
+
+impl Compiler {
+    fn new() -> Self {
+        Compiler { states: Vec::new() }
+    }
+
+    fn push(&mut self, state: State) -> StateId {
+        self.states.push(state);
+        self.states.len() - 1
+    }
+
+    fn patch(&mut self, dangling: Vec<Dangling>, target: StateId) {
+        for d in dangling {
+            match d {
+                Dangling::CharNext(id) => {
+                    if let State::Char(_, next) = &mut self.states[id] {
+                        *next = target;
+                    }
+                },
+                Dangling::Split1(id) => {
+                    if let State::Split(a, _) = &mut self.states[id] {
+                        *a = target;
+                    }
+                },
+                Dangling::Split2(id) => {
+                    if let State::Split(_, b) = &mut self.states[id] {
+                        *b = target;
+                    }
+                },
+                Dangling::StartAnchorNext(id) => {
+                    if let State::StartAnchor(next) = &mut self.states[id] {
+                        *next = target;
+                    }
+                },
+                Dangling::EndAnchorNext(id) => {
+                    if let State::EndAnchor(next) = &mut self.states[id] {
+                        *next = target;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Concatenates already-compiled fragments in sequence, patching each
+    /// one's dangling outs to the next one's entry. An empty sequence
+    /// compiles to a fragment that matches the empty string.
+    fn compile_seq(&mut self, frags: Vec<Frag>) -> Frag {
+        let mut iter = frags.into_iter();
+        let mut acc = match iter.next() {
+            Some(frag) => frag,
+            None => {
+                let id = self.push(State::Split(0, 0));
+                return Frag { start: id, dangling: vec![Dangling::Split1(id), Dangling::Split2(id)] };
+            },
+        };
+        for frag in iter {
+            self.patch(acc.dangling, frag.start);
+            acc = Frag { start: acc.start, dangling: frag.dangling };
+        }
+        acc
+    }
+
+    fn compile_concat(&mut self, exprs: &[Expression]) -> Frag {
+        let frags = exprs.iter().map(|e| self.compile(e)).collect();
+        self.compile_seq(frags)
+    }
+
+    fn compile(&mut self, expr: &Expression) -> Frag {
+        match expr {
+            Expression::Program(_, exprs) => self.compile_concat(exprs),
+            Expression::Concat(exprs) => self.compile_concat(exprs),
+            Expression::Parens(inner) => self.compile(inner),
+
+            Expression::Literal(s) => {
+                let frags = s
+                    .chars()
+                    .map(|c| {
+                        let id = self.push(State::Char(Box::new(move |ch| ch == c), 0));
+                        Frag { start: id, dangling: vec![Dangling::CharNext(id)] }
+                    })
+                    .collect();
+                self.compile_seq(frags)
+            },
+
+            Expression::Bracket(class) => {
+                let class = class.clone();
+                let id = self.push(State::Char(Box::new(move |ch| class.contains(ch)), 0));
+                Frag { start: id, dangling: vec![Dangling::CharNext(id)] }
+            },
+
+            Expression::Anchor(Token::Caret) => {
+                let id = self.push(State::StartAnchor(0));
+                Frag { start: id, dangling: vec![Dangling::StartAnchorNext(id)] }
+            },
+            Expression::Anchor(Token::Dollar) => {
+                let id = self.push(State::EndAnchor(0));
+                Frag { start: id, dangling: vec![Dangling::EndAnchorNext(id)] }
+            },
+            Expression::Anchor(t) => unreachable!("Anchor only ever wraps Caret or Dollar, got {:?}", t),
+
+            Expression::VBar(a, b) => {
+                let fa = self.compile(a);
+                let fb = self.compile(b);
+                let split = self.push(State::Split(fa.start, fb.start));
+                let mut dangling = fa.dangling;
+                dangling.extend(fb.dangling);
+                Frag { start: split, dangling }
+            },
+
+            Expression::UnaryPostfix(Token::KleeneStar, inner) => {
+                let frag = self.compile(inner);
+                let split = self.push(State::Split(frag.start, 0));
+                self.patch(frag.dangling, split);
+                Frag { start: split, dangling: vec![Dangling::Split2(split)] }
+            },
+            Expression::UnaryPostfix(Token::KleenePlus, inner) => {
+                let frag = self.compile(inner);
+                let split = self.push(State::Split(frag.start, 0));
+                self.patch(frag.dangling, split);
+                Frag { start: frag.start, dangling: vec![Dangling::Split2(split)] }
+            },
+            Expression::UnaryPostfix(Token::Question, inner) => {
+                let frag = self.compile(inner);
+                let split = self.push(State::Split(frag.start, 0));
+                let mut dangling = frag.dangling;
+                dangling.push(Dangling::Split2(split));
+                Frag { start: split, dangling }
+            },
+            Expression::UnaryPostfix(t, _) => unreachable!("UnaryPostfix only ever wraps a quantifier token, got {:?}", t),
+
+            Expression::Comma(inner, m, n) => {
+                let mut frags = Vec::new();
+                for _ in 0..*m {
+                    frags.push(self.compile(inner));
+                }
+                if *n == -1 {
+                    let tail = self.compile(inner);
+                    let split = self.push(State::Split(tail.start, 0));
+                    self.patch(tail.dangling, split);
+                    frags.push(Frag { start: split, dangling: vec![Dangling::Split2(split)] });
+                } else {
+                    for _ in *m..*n {
+                        let opt = self.compile(inner);
+                        let split = self.push(State::Split(opt.start, 0));
+                        let mut dangling = vec![Dangling::Split2(split)];
+                        dangling.extend(opt.dangling);
+                        frags.push(Frag { start: split, dangling });
+                    }
+                }
+                self.compile_seq(frags)
+            },
+        }
+    }
+}
+
+/// A Thompson NFA compiled from an `Expression` tree, together with the
+/// primitives (`closure`/`step`/`is_accepting`) needed to drive either a
+/// plain simultaneous-state-set simulation or a lazily-constructed DFA
+/// layered on top (see the `dfa` module).
+#[derive(Debug)]
+pub(crate) struct Nfa {
+    states: Vec<State>,
+    start: StateId,
+}
+
+impl Nfa {
+    /// Compiles `expression` into a Thompson NFA with a single `Match` exit
+    /// state patched onto every dangling fragment output.
+    pub(crate) fn compile(expression: &Expression) -> Nfa {
+        let mut compiler = Compiler::new();
+        let frag = compiler.compile(expression);
+        let match_state = compiler.push(State::Match);
+        compiler.patch(frag.dangling, match_state);
+        Nfa { states: compiler.states, start: frag.start }
+    }
+
+    /// Computes the epsilon-closure of `id` within `at_start`/`at_end`
+    /// context (whether the haystack position is 0 / its full length,
+    /// which gates `^`/`$` anchors), appending every `Char`/`Match` state
+    /// reachable without consuming input to `out`.
+    fn closure_from(&self, id: StateId, at_start: bool, at_end: bool, out: &mut Vec<StateId>, visited: &mut [bool]) {
+        if visited[id] {
+            return;
+        }
+        visited[id] = true;
+        match &self.states[id] {
+            State::Split(a, b) => {
+                self.closure_from(*a, at_start, at_end, out, visited);
+                self.closure_from(*b, at_start, at_end, out, visited);
+            },
+            State::StartAnchor(next) => {
+                if at_start {
+                    self.closure_from(*next, at_start, at_end, out, visited);
+                }
+            },
+            State::EndAnchor(next) => {
+                if at_end {
+                    self.closure_from(*next, at_start, at_end, out, visited);
+                }
+            },
+            State::Char(_, _) | State::Match => out.push(id),
+        }
+    }
+
+    /// The initial DFA/NFA state-set: the epsilon-closure of the start
+    /// state, with `at_end` true only when the haystack is empty.
+    pub(crate) fn closure_from_start(&self, at_end: bool) -> Vec<StateId> {
+        let mut out = Vec::new();
+        self.closure_from(self.start, true, at_end, &mut out, &mut vec![false; self.states.len()]);
+        out.sort_unstable();
+        out.dedup();
+        out
+    }
+
+    /// Advances every `Char` state in `set` that matches `c`, then takes the
+    /// epsilon-closure of the result. `at_end` is true when this step
+    /// consumes the haystack's last character.
+    pub(crate) fn step(&self, set: &[StateId], c: char, at_end: bool) -> Vec<StateId> {
+        let mut out = Vec::new();
+        let mut visited = vec![false; self.states.len()];
+        for &s in set {
+            if let State::Char(matches, next_id) = &self.states[s] {
+                if matches(c) {
+                    self.closure_from(*next_id, false, at_end, &mut out, &mut visited);
+                }
+            }
+        }
+        out.sort_unstable();
+        out.dedup();
+        out
+    }
+
+    /// Whether any state in `set` is the accepting `Match` state.
+    pub(crate) fn is_accepting(&self, set: &[StateId]) -> bool {
+        set.iter().any(|&s| matches!(self.states[s], State::Match))
+    }
+
+    /// Runs Thompson's simultaneous-state-set simulation: at each input
+    /// position, track every NFA state reachable so far, advance them all
+    /// in lockstep per character, and accept if any reaches `Match` once
+    /// the haystack is exhausted. This is O(n·m) with no backtracking.
+    pub(crate) fn simulate(&self, haystack: &str) -> bool {
+        let chars: Vec<char> = haystack.chars().collect();
+        let len = chars.len();
+
+        let mut current = self.closure_from_start(len == 0);
+
+        for (i, &c) in chars.iter().enumerate() {
+            if current.is_empty() {
+                return false;
+            }
+            current = self.step(&current, c, i + 1 == len);
+        }
+
+        self.is_accepting(&current)
+    }
+}
+
+/// Compiles an already-parsed `expression` into a Thompson NFA and runs it
+/// against `haystack`, honoring `^`/`$` anchors as start/end assertions.
+///
+/// Test-only: `Regex` drives the same pipeline through the cached `Dfa`
+/// instead, so this plain-NFA path only exists to let this module's tests
+/// exercise `pattern -> bool` end to end without building a `Dfa`.
+#[cfg(test)]
+fn matches(expression: &Expression, haystack: &str) -> bool {
+    Nfa::compile(expression).simulate(haystack)
+}
+
+/// Lexes, parses, compiles, and runs `pattern` as a Thompson NFA against
+/// `haystack`, honoring `^`/`$` anchors as start/end assertions. Test-only,
+/// see `matches` above.
+#[cfg(test)]
+fn is_match(pattern: &str, haystack: &str) -> Result<bool, ParseError> {
+    let tokens = crate::lexer::lex(pattern).map_err(ParseError::Lex)?;
+    let expression = Parser::new(pattern, tokens).parse()?;
+    Ok(matches(&expression, haystack))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_match;
+
+    #[test]
+    fn nfa_kleene_star_then_literal() {
+        assert!(is_match("a*b", "aaab").unwrap());
+        assert!(is_match("a*b", "b").unwrap());
+        assert!(!is_match("a*b", "aaa").unwrap());
+    }
+
+    #[test]
+    fn nfa_grouped_alternation_kleene_star() {
+        assert!(is_match("(a|b)*c", "ababc").unwrap());
+        assert!(is_match("(a|b)*c", "c").unwrap());
+        assert!(!is_match("(a|b)*c", "ababd").unwrap());
+    }
+
+    #[test]
+    fn nfa_bracket_range_kleene_plus() {
+        assert!(is_match("[a-z]+", "hello").unwrap());
+        assert!(!is_match("[a-z]+", "HELLO").unwrap());
+        assert!(!is_match("[a-z]+", "").unwrap());
+    }
+
+    #[test]
+    fn nfa_anchors() {
+        assert!(is_match("^a$", "a").unwrap());
+        assert!(!is_match("^a$", "ab").unwrap());
+        assert!(!is_match("^a$", "ba").unwrap());
+    }
+
+    #[test]
+    fn nfa_bounded_repetition() {
+        assert!(is_match("a{2,3}", "aa").unwrap());
+        assert!(is_match("a{2,3}", "aaa").unwrap());
+        assert!(!is_match("a{2,3}", "a").unwrap());
+        assert!(!is_match("a{2,3}", "aaaa").unwrap());
+    }
+
+    #[test]
+    fn nfa_escape_shorthand_classes() {
+        assert!(is_match(r"\d+", "1234").unwrap());
+        assert!(!is_match(r"\d+", "abcd").unwrap());
+        assert!(is_match(r"\D+", "abcd").unwrap());
+        assert!(is_match(r"\w+", "a_1").unwrap());
+        assert!(!is_match(r"\w+", "a 1").unwrap());
+        assert!(is_match(r"\s+", " \t\n").unwrap());
+        assert!(!is_match(r"\S+", " ").unwrap());
+    }
+
+    #[test]
+    fn nfa_escape_literal_metacharacter() {
+        assert!(is_match(r"a\.b", "a.b").unwrap());
+        assert!(!is_match(r"a\.b", "axb").unwrap());
+    }
+
+    #[test]
+    fn nfa_dot_matches_any_char() {
+        assert!(is_match("a.b", "axb").unwrap());
+        assert!(is_match("a.b", "a.b").unwrap());
+        assert!(!is_match("a.b", "ab").unwrap());
+    }
+}
@@ -1,16 +1,85 @@
-use std::rc::{Rc, Weak};
+use crate::charclass::CharClass;
+use crate::lexer::{LexError, Span, Token};
 
-use crate::lexer::Token;
+/// Errors produced while parsing a token stream into an AST.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// Lexing the expression failed before parsing could begin.
+    Lex(LexError),
+    /// A token appeared where none of the grammar productions expected it.
+    UnexpectedToken(Token, Span),
+    /// An atom was expected but a `|`, a `)`, or the end of the token stream
+    /// came first. The span is that of the `|`/`)` token, or at end of input a
+    /// single-column marker just past the last character of the expression.
+    MissingOperand(Span),
+    /// A `(` at `Span` was never matched by a closing `)`.
+    UnbalancedParen(Span),
+    /// A `[` at `Span` was never matched by a closing `]`.
+    ExpectedClosingBracket(Span),
+    /// A `[lo-hi]` range had `hi` sorting before `lo`, e.g. `[z-a]`.
+    DescendingBracketRange(char, char),
+    /// A `{m,n}` bound had a non-integer where a repetition count was expected.
+    InvalidRepetitionInt,
+    /// A `{m,n}` repetition brace was never closed with a `}`.
+    UnterminatedRepetition,
+    /// A `{m,n}` bound had `n` sorting before `m`, e.g. `{5,2}`.
+    DescendingRepetitionRange(i32, i32),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Lex(e) => write!(f, "{}", e),
+            ParseError::UnexpectedToken(t, _) => write!(f, "Unexpected token: {:?}", t),
+            ParseError::MissingOperand(_) => write!(f, "No child to apply operator to"),
+            ParseError::UnbalancedParen(_) => write!(f, "Unbalanced parenthesis"),
+            ParseError::ExpectedClosingBracket(_) => write!(f, "Expected closing bracket"),
+            ParseError::DescendingBracketRange(lo, hi) => {
+                write!(f, "Descending bracket range: '{}-{}'", lo, hi)
+            },
+            ParseError::InvalidRepetitionInt => write!(f, "Invalid repetition count"),
+            ParseError::UnterminatedRepetition => write!(f, "Unterminated repetition brace"),
+            ParseError::DescendingRepetitionRange(m, n) => {
+                write!(f, "Descending repetition range: '{{{},{}}}'", m, n)
+            },
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    /// The span to underline when rendering this error, for the variants
+    /// that carry one.
+    fn span(&self) -> Option<Span> {
+        match self {
+            ParseError::UnexpectedToken(_, span) => Some(*span),
+            ParseError::MissingOperand(span) => Some(*span),
+            ParseError::UnbalancedParen(span) => Some(*span),
+            ParseError::ExpectedClosingBracket(span) => Some(*span),
+            ParseError::Lex(_)
+            | ParseError::DescendingBracketRange(_, _)
+            | ParseError::InvalidRepetitionInt
+            | ParseError::UnterminatedRepetition
+            | ParseError::DescendingRepetitionRange(_, _) => None,
+        }
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
-enum Expression {
+pub(crate) enum Expression {
     /// Program is the root of the regex expression tree.
     /// Its data is simply the raw input regex itself alongside the expressions
     /// that make up the regex.
     /// It should never be referenced by other expressions.
     /// For example, if the regex is "a*b", the Program expression would contain "a*b"
     /// and the expressions would be [UnaryPostfix(KleeneStar, Literal("a")), Literal("b")].
-    Program(String, Vec<Box<Expression>>),
+    Program(String, Vec<Expression>),
+
+    /// A sequence of adjacent expressions that must match back-to-back, e.g. the
+    /// "ab" in "ab*|c" parses as `Concat([Literal("a"), UnaryPostfix(KleeneStar, Literal("b"))])`.
+    /// Unlike `Program`, `Concat` may appear nested, e.g. inside `Parens` or `VBar`.
+    Concat(Vec<Expression>),
 
     /// Unary expressions are those that apply to a single expression.
     /// Token is either Kleene star, Kleene plus, or question.
@@ -21,33 +90,21 @@ enum Expression {
     /// This is useful for grouping and precedence.
     Parens(Box<Expression>),
 
-    /// A bracket expression. Matches a single character that is contained within the brackets.
+    /// A bracket expression or escape shorthand class. Matches a single
+    /// character accepted by the `CharClass`.
     /// For example, [abc] matches "a", "b", or "c".
     /// [a-z] specifies a range which matches any lowercase letter from "a" to "z".
     /// These forms can be mixed: [abcx-z] matches "a", "b", "c", "x", "y", or "z", as does [a-cx-z].
     /// The - character is treated as a literal character if it is the last or the first
     /// (after the ^, if present) character within the brackets: [abc-], [-abc], [^-abc]
-    /// Backslash escapes are not allowed. The ] character can be included in a bracket
-    /// expression if it is the first (after the ^, if present) character: []abc], [^]abc].
-    /// [abcx-z] parses into something like [a|b|c|x-z].
-    ///
-    /// Since the | operator is commutative, the parse tree can look something like
-    /// this:
+    /// The ] character can be included in a bracket expression if it is the
+    /// first (after the ^, if present) character: []abc], [^]abc]. A leading
+    /// `^` negates the class, complementing the `CharClass`.
     ///
-    ///     Bracket
-    ///     ├── VBar
-    ///     │   ├── Literal "a"
-    ///     │   ├── VBar
-    ///     │       ├── Literal "b"
-    ///     │       ├── VBar
-    ///     │           ├── Literal "c"
-    ///     │           └── Range "x-z"
-    Bracket(Box<Expression>),
-
-    // A range expression matches any character that is within the specified range.
-    // For example, Range('a', 'z') matches any lowercase letter from "a" to "z".
-    // The range is inclusive, meaning both endpoints are included.
-    Range(char, char),
+    /// The escape shorthand classes `\d`, `\w`, `\s` and their negations
+    /// `\D`, `\W`, `\S` compile to the same node, since they're just
+    /// pre-built `CharClass`es rather than ones parsed from `[...]`.
+    Bracket(CharClass),
 
     // A literal expression matches a single character or a sequence of characters.
     // For example, Literal("abc") matches the string "abc".
@@ -61,6 +118,10 @@ enum Expression {
     //     └── Literal "b"
     VBar(Box<Expression>, Box<Expression>),
 
+    /// A `^` or `$` anchor, asserting the match occurs at the start or end of
+    /// the haystack respectively, without consuming a character.
+    Anchor(Token),
+
     // A comma (,) is used in regex to specify a range of repetitions.
     // For example, a{2,5} matches "aa", "aaa", "aaaa", or "aaaaa".
     // The parse tree for this would look like:
@@ -72,202 +133,604 @@ enum Expression {
     Comma(Box<Expression>, i32, i32),
 }
 
-struct Parser {
+pub(crate) struct Parser {
     expression: String,
-    tokens: Vec<Token>,
+    tokens: Vec<(Token, Span)>,
     current_token_idx: usize,
-    current_node: Option<ASTNode>,
 }
 
 impl Parser {
-    fn new(expression: &str, tokens: Vec<Token>) -> Self {
+    pub(crate) fn new(expression: &str, tokens: Vec<(Token, Span)>) -> Self {
         Parser {
             expression: expression.to_string(),
             tokens,
             current_token_idx: 0,
-            current_node: None,
         }
     }
 
-    fn parse(&mut self) -> Result<(), String> {
-        self.current_node = Some(ASTNode::new(Some(Token::Program)));
-        while self.current_token_idx < self.tokens.len() {
-            match self.parse_next() {
-                Ok(_) => {},
-                Err(e) => return Err(e),
+    /// Renders `err` followed by a caret-underline snippet pointing at the span
+    /// it occurred at within the original expression, for errors that carry one.
+    pub(crate) fn describe_error(&self, err: &ParseError) -> String {
+        match err.span() {
+            Some(span) => format!("{}\n{}", err, span.render_caret(&self.expression)),
+            None => err.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<(Token, Span)> {
+        self.tokens.get(self.current_token_idx).cloned()
+    }
+
+    fn advance(&mut self) -> Option<(Token, Span)> {
+        let token = self.peek();
+        if token.is_some() {
+            self.current_token_idx += 1;
+        }
+        token
+    }
+
+    /// Parses the whole token stream into an `Expression::Program`, using the
+    /// standard regex precedence levels (loosest to tightest): alternation,
+    /// concatenation, quantifier, atom.
+    pub(crate) fn parse(&mut self) -> Result<Expression, ParseError> {
+        let body = self.parse_alternation()?;
+        if let Some((token, span)) = self.peek() {
+            return Err(ParseError::UnexpectedToken(token, span));
+        }
+        Ok(Expression::Program(self.expression.clone(), vec![body]))
+    }
+
+    /// alternation := concatenation ('|' concatenation)*
+    fn parse_alternation(&mut self) -> Result<Expression, ParseError> {
+        let mut expr = self.parse_concatenation()?;
+        while matches!(self.peek(), Some((Token::VBar, _))) {
+            self.advance();
+            let rhs = self.parse_concatenation()?;
+            expr = Expression::VBar(Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    /// concatenation := quantifier+, stopping at '|', ')', or end of input
+    fn parse_concatenation(&mut self) -> Result<Expression, ParseError> {
+        let mut atoms = Vec::new();
+        while let Some((token, _)) = self.peek() {
+            if matches!(token, Token::VBar | Token::RParen) {
+                break;
             }
+            atoms.push(self.parse_quantifier()?);
+        }
+        match atoms.len() {
+            0 => Err(self.missing_operand_error()),
+            1 => Ok(atoms.into_iter().next().unwrap()),
+            _ => Ok(Expression::Concat(atoms)),
         }
-        Ok(())
     }
 
-    fn parse_next(&mut self) -> Result<(), String> {
-        match self.tokens.get(self.current_token_idx) {
-            Some(token) => {
-                match token {
-                    Token::Literal(_) => {
-                        self.parse_literal(token.clone())
-                    },
-                    _ if token.is_unary_postfix() => {
-                        self.parse_unary_postfix(token.clone())
+    /// The error to raise when an atom was expected but the token stream
+    /// offers none to consume: a `|`/`)`/end-of-input sitting where an
+    /// operand should be, e.g. the empty alternative in `"a*|"`, `"()"`, or
+    /// `"a||b"`.
+    fn missing_operand_error(&self) -> ParseError {
+        match self.peek() {
+            Some((_, span)) => ParseError::MissingOperand(span),
+            None => {
+                let end = self.expression.len();
+                ParseError::MissingOperand(Span::new(end, end + 1))
+            },
+        }
+    }
+
+    /// quantifier := atom (('*' | '+' | '?') | '{' m (',' n?)? '}')*
+    fn parse_quantifier(&mut self) -> Result<Expression, ParseError> {
+        let mut expr = self.parse_atom()?;
+        loop {
+            match self.peek() {
+                Some((token, _)) if token.is_unary_postfix() => {
+                    self.advance();
+                    expr = Expression::UnaryPostfix(token, Box::new(expr));
+                },
+                Some((Token::LBrace, _)) => {
+                    let (m, n) = self.parse_brace_repetition()?;
+                    expr = Expression::Comma(Box::new(expr), m, n);
+                },
+                _ => break,
+            }
+        }
+        Ok(expr)
+    }
+
+    /// Parses a `{m}`, `{m,}`, or `{m,n}` bounded-repetition brace, entered just
+    /// before the opening `{`. `n` is `-1` to mean "unbounded" for the `{m,}` form.
+    fn parse_brace_repetition(&mut self) -> Result<(i32, i32), ParseError> {
+        self.advance(); // consume '{'
+        let m = self.parse_brace_int()?;
+        match self.advance() {
+            Some((Token::RBrace, _)) => Ok((m, m)),
+            Some((Token::Comma, _)) => {
+                if matches!(self.peek(), Some((Token::RBrace, _))) {
+                    self.advance();
+                    return Ok((m, -1));
+                }
+                let n = self.parse_brace_int()?;
+                match self.advance() {
+                    Some((Token::RBrace, _)) if n < m => {
+                        Err(ParseError::DescendingRepetitionRange(m, n))
                     },
-                    Token::LParen => {
-                        self.parse_parens()
-                    }
-                    _ => Err(format!("Unexpected token: {:?}", token)),
+                    Some((Token::RBrace, _)) => Ok((m, n)),
+                    Some((token, span)) => Err(ParseError::UnexpectedToken(token, span)),
+                    None => Err(ParseError::UnterminatedRepetition),
                 }
             },
-            None => Err("No tokens to parse".into()),
+            Some((token, span)) => Err(ParseError::UnexpectedToken(token, span)),
+            None => Err(ParseError::UnterminatedRepetition),
         }
     }
 
-    fn parse_parens(&mut self) -> Result<(), String> {
-        Ok(())
+    /// Reads the run of single-digit `Literal` tokens the lexer produces for a
+    /// multi-digit number (e.g. `{10,20}` lexes digit-by-digit) and parses them
+    /// back into an `i32`.
+    fn parse_brace_int(&mut self) -> Result<i32, ParseError> {
+        let mut digits = String::new();
+        while let Some((Token::Literal(s), _)) = self.peek() {
+            if s.chars().next().is_some_and(|c| c.is_ascii_digit()) {
+                digits.push_str(&s);
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        digits.parse::<i32>().map_err(|_| ParseError::InvalidRepetitionInt)
     }
 
-    fn parse_literal(&mut self, token: Token) -> Result<(), String> {
-        match token {
-            Token::Literal(_) => {
-                if let Some(ref mut node) = self.current_node {
-                    node.add_child(ASTNode::new(Some(token)));
+    /// atom := Literal | '(' alternation ')' | bracket | escape | '^' | '$' | '.'
+    fn parse_atom(&mut self) -> Result<Expression, ParseError> {
+        match self.advance() {
+            Some((Token::Literal(c), _)) => Ok(Expression::Literal(c)),
+            Some((Token::LParen, open)) => {
+                let inner = self.parse_alternation()?;
+                match self.advance() {
+                    Some((Token::RParen, _)) => Ok(Expression::Parens(Box::new(inner))),
+                    _ => Err(ParseError::UnbalancedParen(open)),
                 }
-                self.current_token_idx += 1;
-                Ok(())
             },
-            _ => Err(format!("Expected a literal, found: {:?}", token)),
+            Some((Token::LBracket, open)) => self.parse_bracket(open),
+            Some((Token::Backslash(c), _)) => Ok(escape_expression(c)),
+            Some((Token::Caret, _)) => Ok(Expression::Anchor(Token::Caret)),
+            Some((Token::Dollar, _)) => Ok(Expression::Anchor(Token::Dollar)),
+            Some((Token::Dot, _)) => Ok(Expression::Bracket(CharClass::any())),
+            Some((token, span)) => Err(ParseError::UnexpectedToken(token, span)),
+            None => Err(self.missing_operand_error()),
         }
     }
 
-    fn parse_unary_postfix(&mut self, token: Token) -> Result<(), String> {
-        match token {
-            _ if token.is_unary_postfix() => {
-                // unary postfix operators like Kleene star, plus, or question
-                // apply to the last node in the current AST.
-                match self.current_node {
-                    None => return Err("No current node to apply Kleene star to".into()),
-                    Some(ref mut node) => {
-                        let child = node.pop_child().ok_or("No child to apply Kleene star to")?;
-                        let mut unary_postfix_node = ASTNode::new(Some(token));
-                        unary_postfix_node.add_child(child);
-                        node.add_child(unary_postfix_node);
-                        self.current_token_idx += 1;
-                        Ok(())
-                    },
+    /// bracket := '[' '^'? ']'? (member)* ']'
+    ///
+    /// Entered just after the opening `[` has been consumed. Honors the bracket
+    /// grammar's quirks: a leading `^` negates the class; a `]` as the first
+    /// member (after the optional `^`) is a literal `]` rather than the
+    /// closing bracket; a `-` is a literal dash at the first or last position
+    /// and joins its two neighbours into a range everywhere else.
+    ///
+    /// `open` is the span of the `[` that was just consumed, used to point at
+    /// the offending bracket if it's never closed.
+    fn parse_bracket(&mut self, open: Span) -> Result<Expression, ParseError> {
+        let negated = matches!(self.peek(), Some((Token::Caret, _)));
+        if negated {
+            self.advance();
+        }
+
+        let mut members = Vec::new();
+        let mut first = true;
+        loop {
+            match self.peek() {
+                None => return Err(ParseError::ExpectedClosingBracket(open)),
+                Some((Token::RBracket, _)) if !first => {
+                    self.advance();
+                    break;
+                },
+                Some((token, _)) => {
+                    self.advance();
+                    members.push(bracket_member_char(token));
+                },
+            }
+            first = false;
+        }
+
+        if members.is_empty() {
+            return Err(ParseError::ExpectedClosingBracket(open));
+        }
+
+        // Fold '-' into a range when it sits strictly between two other members;
+        // a '-' at the first/last position is a literal dash member instead.
+        let mut class = CharClass::empty();
+        let mut i = 0;
+        while i < members.len() {
+            if members[i] == '-' && i > 0 && i + 1 < members.len() {
+                let lo = members[i - 1];
+                let hi = members[i + 1];
+                if hi < lo {
+                    return Err(ParseError::DescendingBracketRange(lo, hi));
                 }
-            },
-            _ => Err(format!("Expected unary postfix token, found: {:?}", token)),
+                class = class.union(CharClass::range(lo, hi));
+                i += 1;
+            } else {
+                class = class.union(CharClass::single(members[i]));
+            }
+            i += 1;
         }
+
+        if negated {
+            class = class.negate();
+        }
+
+        Ok(Expression::Bracket(class))
     }
 }
 
-struct ASTNode {
-    parent: Option<Weak<Rc<ASTNode>>>,
-    children: Vec<Box<ASTNode>>,
-    op: Option<Token>,
+/// Expands an escaped character to the `Expression` it stands for: the
+/// shorthand classes `\d \D \w \W \s \S` become pre-built `CharClass`es,
+/// and everything else (`\.`, `\*`, `\(`, ...) is just a literal match on
+/// that character.
+fn escape_expression(c: char) -> Expression {
+    match c {
+        'd' => Expression::Bracket(CharClass::digit()),
+        'D' => Expression::Bracket(CharClass::digit().negate()),
+        'w' => Expression::Bracket(CharClass::word()),
+        'W' => Expression::Bracket(CharClass::word().negate()),
+        's' => Expression::Bracket(CharClass::whitespace()),
+        'S' => Expression::Bracket(CharClass::whitespace().negate()),
+        _ => Expression::Literal(c.to_string()),
+    }
 }
 
-impl ASTNode {
-    fn new(op: Option<Token>) -> Self {
-        ASTNode {
-            parent: None,
-            children: Vec::new(),
-            op,
+/// The literal character a token represents when it appears inside a `[...]`
+/// bracket expression, where only `^`, `-`, and `]` carry special meaning.
+fn bracket_member_char(token: Token) -> char {
+    match token {
+        Token::Literal(s) => s.chars().next().expect("lexer never emits an empty Literal"),
+        Token::Backslash(c) => c,
+        Token::Dash => '-',
+        Token::Dot => '.',
+        Token::Caret => '^',
+        Token::Dollar => '$',
+        Token::LParen => '(',
+        Token::RParen => ')',
+        Token::LBrace => '{',
+        Token::RBrace => '}',
+        Token::Comma => ',',
+        Token::KleeneStar => '*',
+        Token::KleenePlus => '+',
+        Token::Question => '?',
+        Token::VBar => '|',
+        Token::LBracket => '[',
+        Token::RBracket => ']',
+        Token::Program => unreachable!("Program token never appears in a lexed token stream"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::charclass::CharClass;
+    use crate::parser::{Expression, ParseError, Parser};
+    use crate::lexer::{lex, Token};
+
+    fn parse(input: &str) -> Expression {
+        let tokens = lex(input).unwrap();
+        Parser::new(input, tokens).parse().unwrap()
+    }
+
+    fn body(program: &Expression) -> &Expression {
+        match program {
+            Expression::Program(_, exprs) => &exprs[0],
+            _ => panic!("expected Expression::Program, got {:?}", program),
         }
     }
 
-    fn add_child(&mut self, child: ASTNode) {
-        self.children.push(Box::new(child));
+    #[test]
+    fn regex_parse_unary_postfix_binds_single_char() {
+        // "ab*" must bind the star to "b" alone, not to the merged literal "ab".
+        let program = parse("ab*");
+        assert_eq!(
+            body(&program),
+            &Expression::Concat(vec![
+                Expression::Literal("a".into()),
+                Expression::UnaryPostfix(Token::KleeneStar, Box::new(Expression::Literal("b".into()))),
+            ]),
+        );
     }
 
-    fn pop_child(&mut self) -> Option<ASTNode> {
-        self.children.pop().map(|child| *child)
+    #[test]
+    fn regex_parse_unary_postfix_binds_single_char_plus() {
+        // "abc+" must bind the plus to "c" alone.
+        let program = parse("abc+");
+        assert_eq!(
+            body(&program),
+            &Expression::Concat(vec![
+                Expression::Literal("a".into()),
+                Expression::Literal("b".into()),
+                Expression::UnaryPostfix(Token::KleenePlus, Box::new(Expression::Literal("c".into()))),
+            ]),
+        );
     }
-}
 
-struct AST {
-    root: Option<ASTNode>,
-}
+    #[test]
+    fn regex_parse_unary_postfix_binds_single_char_question() {
+        // "xy?z" must bind the question mark to "y" alone, leaving "x" and "z" untouched.
+        let program = parse("xy?z");
+        assert_eq!(
+            body(&program),
+            &Expression::Concat(vec![
+                Expression::Literal("x".into()),
+                Expression::UnaryPostfix(Token::Question, Box::new(Expression::Literal("y".into()))),
+                Expression::Literal("z".into()),
+            ]),
+        );
+    }
 
-impl AST {
-    fn new() -> Self {
-        AST {
-            root: None,
-        }
+    #[test]
+    fn regex_parse_alternation() {
+        // "a|b" should be VBar(Literal("a"), Literal("b")).
+        let program = parse("a|b");
+        assert_eq!(
+            body(&program),
+            &Expression::VBar(
+                Box::new(Expression::Literal("a".into())),
+                Box::new(Expression::Literal("b".into())),
+            ),
+        );
     }
 
-    fn add_node(&mut self, node: ASTNode) {
-        match self.root {
-            Some(ref mut root) => root.add_child(node),
-            None => self.root = Some(node),
-        }
+    #[test]
+    fn regex_parse_grouping_then_concatenation() {
+        // "(a|b)c" should group the alternation before concatenating with "c".
+        let program = parse("(a|b)c");
+        assert_eq!(
+            body(&program),
+            &Expression::Concat(vec![
+                Expression::Parens(Box::new(Expression::VBar(
+                    Box::new(Expression::Literal("a".into())),
+                    Box::new(Expression::Literal("b".into())),
+                ))),
+                Expression::Literal("c".into()),
+            ]),
+        );
     }
 
-    fn visualize(&self) -> String {
-        // Visualize the AST as a string with indentation
-        fn visualize_node(node: &ASTNode, depth: usize) -> String {
-            let indent = "  ".repeat(depth);
-            let mut result = String::new();
-            if let Some(ref op) = node.op {
-                result.push_str(&format!("{}{:?}\n", indent, op));
-            }
-            for child in &node.children {
-                result.push_str(&visualize_node(child, depth + 1));
-            }
-            result
-        }
-        if let Some(ref root) = self.root {
-            visualize_node(root, 0)
-        } else {
-            String::from("Empty AST")
-        }
+    #[test]
+    fn regex_parse_alternation_binds_looser_than_concatenation_and_quantifier() {
+        // "a|bc*" should be VBar(Literal("a"), Concat(Literal("b"), UnaryPostfix(*, Literal("c")))),
+        // not Concat(VBar(a,b), UnaryPostfix(*, c)).
+        let program = parse("a|bc*");
+        assert_eq!(
+            body(&program),
+            &Expression::VBar(
+                Box::new(Expression::Literal("a".into())),
+                Box::new(Expression::Concat(vec![
+                    Expression::Literal("b".into()),
+                    Expression::UnaryPostfix(Token::KleeneStar, Box::new(Expression::Literal("c".into()))),
+                ])),
+            ),
+        );
     }
-}
 
-mod tests {
-    use crate::parser::Parser;
-    use crate::lexer::{lex, Token};
+    #[test]
+    fn regex_parse_missing_operand_is_typed() {
+        let input = "a*|";
+        let tokens = lex(input).unwrap();
+        let mut parser = Parser::new(input, tokens);
+        let err = parser.parse().unwrap_err();
+        assert!(matches!(err, ParseError::MissingOperand(_)));
+    }
 
     #[test]
-    fn regex_parse_unary_postfix() {
-        let mut input = "a*b";
-        let kleene_star_tokens = lex(input).unwrap();
-        let mut kleene_star_parser = Parser::new(input, kleene_star_tokens);
-        assert!(kleene_star_parser.parse().is_ok());
-        if let Some(ast) = kleene_star_parser.current_node {
-            assert_eq!(ast.op, Some(Token::Program));
-            assert_eq!(ast.children.len(), 2);
-            if let Some(child) = ast.children.first() {
-                assert_eq!(child.op, Some(Token::KleeneStar));
-            }
-        } else {
-            panic!("Expected a non-empty AST");
-        }
+    fn regex_parse_missing_operand_points_at_end_of_input() {
+        let input = "a*|";
+        let tokens = lex(input).unwrap();
+        let mut parser = Parser::new(input, tokens);
+        let err = parser.parse().unwrap_err();
+        assert!(parser.describe_error(&err).ends_with("a*|\n   ^"));
+    }
 
-        input = "a+b";
-        let kleene_plus_tokens = lex(input).unwrap();
-        let mut kleene_plus_parser = Parser::new(input, kleene_plus_tokens);
-        assert!(kleene_plus_parser.parse().is_ok());
-        if let Some(ast2) = kleene_plus_parser.current_node {
-            assert_eq!(ast2.op, Some(Token::Program));
-            assert_eq!(ast2.children.len(), 2);
-            if let Some(child) = ast2.children.first() {
-                assert_eq!(child.op, Some(Token::KleenePlus));
-            }
-        } else {
-            panic!("Expected a non-empty AST");
+    #[test]
+    fn regex_parse_unbalanced_paren() {
+        let input = "(a|b";
+        let tokens = lex(input).unwrap();
+        let mut parser = Parser::new(input, tokens);
+        let err = parser.parse().unwrap_err();
+        assert_eq!(err, ParseError::UnbalancedParen(crate::lexer::Span::new(0, 1)));
+    }
+
+    #[test]
+    fn regex_parse_unexpected_token_points_at_span() {
+        let input = "a**)";
+        let tokens = lex(input).unwrap();
+        let mut parser = Parser::new(input, tokens);
+        let err = parser.parse().unwrap_err();
+        assert!(parser.describe_error(&err).ends_with("a**)\n   ^"));
+    }
+
+    /// Unwraps the `CharClass` from a parsed program that's a single `Bracket` atom.
+    fn bracket_class(program: &Expression) -> &CharClass {
+        match body(program) {
+            Expression::Bracket(class) => class,
+            other => panic!("expected Expression::Bracket, got {:?}", other),
         }
+    }
 
-        input = "a?b";
-        let question_tokens = lex(input).unwrap();
-        let mut question_parser = Parser::new(input,question_tokens);
-        assert!(question_parser.parse().is_ok());
-        if let Some(ast3) = question_parser.current_node {
-            assert_eq!(ast3.op, Some(Token::Program));
-            assert_eq!(ast3.children.len(), 2);
-            if let Some(child) = ast3.children.first() {
-                assert_eq!(child.op, Some(Token::Question));
-            }
-        } else {
-            panic!("Expected a non-empty AST");
+    #[test]
+    fn regex_parse_bracket_literals() {
+        let program = parse("[abc]");
+        assert_eq!(
+            bracket_class(&program),
+            &CharClass::single('a').union(CharClass::single('b')).union(CharClass::single('c')),
+        );
+    }
+
+    #[test]
+    fn regex_parse_bracket_range() {
+        let program = parse("[a-z]");
+        assert_eq!(bracket_class(&program), &CharClass::range('a', 'z'));
+    }
+
+    #[test]
+    fn regex_parse_bracket_negated() {
+        let program = parse("[^a-z]");
+        assert_eq!(bracket_class(&program), &CharClass::range('a', 'z').negate());
+    }
+
+    #[test]
+    fn regex_parse_bracket_mixed_ranges_and_literals() {
+        // "[abcx-z]" parses into [a|b|c|x-z], per the doc comment on Bracket.
+        let program = parse("[abcx-z]");
+        assert_eq!(
+            bracket_class(&program),
+            &CharClass::single('a')
+                .union(CharClass::single('b'))
+                .union(CharClass::single('c'))
+                .union(CharClass::range('x', 'z')),
+        );
+    }
+
+    #[test]
+    fn regex_parse_bracket_leading_trailing_dash_is_literal() {
+        // A leading/trailing dash must surface as a literal '-' member, never
+        // folded into a range. `.contains('-')` alone can't tell the
+        // difference for the negated case, since negation flips membership
+        // for every char alike, so compare against the whole expected class.
+        let plain = CharClass::single('-')
+            .union(CharClass::single('a'))
+            .union(CharClass::single('b'))
+            .union(CharClass::single('c'));
+        for input in ["[-abc]", "[abc-]"] {
+            assert_eq!(bracket_class(&parse(input)), &plain, "{input}");
         }
+        assert_eq!(bracket_class(&parse("[^-abc]")), &plain.negate());
+    }
+
+    #[test]
+    fn regex_parse_bracket_leading_rbracket_is_literal() {
+        let plain = CharClass::single(']')
+            .union(CharClass::single('a'))
+            .union(CharClass::single('b'))
+            .union(CharClass::single('c'));
+        assert_eq!(bracket_class(&parse("[]abc]")), &plain);
+        assert_eq!(bracket_class(&parse("[^]abc]")), &plain.negate());
+    }
+
+    #[test]
+    fn regex_parse_escape_shorthand_classes() {
+        assert_eq!(bracket_class(&parse(r"\d")), &CharClass::digit());
+        assert_eq!(bracket_class(&parse(r"\D")), &CharClass::digit().negate());
+        assert_eq!(bracket_class(&parse(r"\w")), &CharClass::word());
+        assert_eq!(bracket_class(&parse(r"\W")), &CharClass::word().negate());
+        assert_eq!(bracket_class(&parse(r"\s")), &CharClass::whitespace());
+        assert_eq!(bracket_class(&parse(r"\S")), &CharClass::whitespace().negate());
+    }
+
+    #[test]
+    fn regex_parse_escape_non_shorthand_is_a_literal() {
+        let program = parse(r"\.");
+        assert_eq!(body(&program), &Expression::Literal(".".into()));
+    }
+
+    #[test]
+    fn regex_parse_dot_is_any_char() {
+        let program = parse(".");
+        assert_eq!(bracket_class(&program), &CharClass::any());
+    }
+
+    #[test]
+    fn regex_parse_bracket_descending_range_is_an_error() {
+        let input = "[z-a]";
+        let tokens = lex(input).unwrap();
+        let mut parser = Parser::new(input, tokens);
+        assert_eq!(
+            parser.parse().unwrap_err(),
+            ParseError::DescendingBracketRange('z', 'a'),
+        );
+    }
+
+    #[test]
+    fn regex_parse_bracket_unterminated_is_an_error() {
+        let input = "[abc";
+        let tokens = lex(input).unwrap();
+        let mut parser = Parser::new(input, tokens);
+        assert_eq!(
+            parser.parse().unwrap_err(),
+            ParseError::ExpectedClosingBracket(crate::lexer::Span::new(0, 1)),
+        );
+    }
+
+    #[test]
+    fn regex_parse_brace_exact_repetition() {
+        let program = parse("a{3}");
+        assert_eq!(
+            body(&program),
+            &Expression::Comma(Box::new(Expression::Literal("a".into())), 3, 3),
+        );
+    }
+
+    #[test]
+    fn regex_parse_brace_unbounded_repetition() {
+        let program = parse("a{2,}");
+        assert_eq!(
+            body(&program),
+            &Expression::Comma(Box::new(Expression::Literal("a".into())), 2, -1),
+        );
+    }
+
+    #[test]
+    fn regex_parse_brace_bounded_repetition() {
+        let program = parse("a{2,5}");
+        assert_eq!(
+            body(&program),
+            &Expression::Comma(Box::new(Expression::Literal("a".into())), 2, 5),
+        );
+    }
+
+    #[test]
+    fn regex_parse_quantifier_binds_tighter_than_alternation_on_both_sides() {
+        // "ab*|cd" should group as (a·b*)|(c·d), i.e. VBar of two Concats,
+        // never Concat(a, VBar(b*, c), d).
+        let program = parse("ab*|cd");
+        assert_eq!(
+            body(&program),
+            &Expression::VBar(
+                Box::new(Expression::Concat(vec![
+                    Expression::Literal("a".into()),
+                    Expression::UnaryPostfix(Token::KleeneStar, Box::new(Expression::Literal("b".into()))),
+                ])),
+                Box::new(Expression::Concat(vec![
+                    Expression::Literal("c".into()),
+                    Expression::Literal("d".into()),
+                ])),
+            ),
+        );
+    }
+
+    #[test]
+    fn regex_parse_anchors() {
+        // "^a$" should be Concat(Anchor(Caret), Literal("a"), Anchor(Dollar)).
+        let program = parse("^a$");
+        assert_eq!(
+            body(&program),
+            &Expression::Concat(vec![
+                Expression::Anchor(Token::Caret),
+                Expression::Literal("a".into()),
+                Expression::Anchor(Token::Dollar),
+            ]),
+        );
+    }
+
+    #[test]
+    fn regex_parse_brace_descending_range_is_an_error() {
+        let input = "a{5,2}";
+        let tokens = lex(input).unwrap();
+        let mut parser = Parser::new(input, tokens);
+        assert_eq!(
+            parser.parse().unwrap_err(),
+            ParseError::DescendingRepetitionRange(5, 2),
+        );
     }
 }